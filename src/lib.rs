@@ -1,30 +1,96 @@
-use num_traits::Num;
+use num_traits::{Num, NumCast};
 use std::time::{self, Instant};
 
+/// Shorthand for the original all-`f64` range, for call sites where float
+/// literals alone leave the element type ambiguous (e.g. calling `abs`).
+type Range64 = Range<f64>;
 
-#[derive(Debug)]
-enum Deviation {
-    Low(f64),
-    High(f64),
+#[derive(Debug, Clone)]
+enum Deviation<T> {
+    Low(T),
+    High(T),
+    /// The mapped value changed too fast; carries the offending slope (mapped
+    /// units per second) so a rate violation is distinguishable from a level one.
+    Rate(T),
 }
 
-#[derive(Debug)]
-enum State {
-    Nominal(f64),
-    Alert(f64, Deviation),
-    Error(f64, Deviation),
+#[derive(Debug, Clone)]
+enum State<T> {
+    Nominal(T),
+    Alert(T, Deviation<T>, Option<Deviation<T>>),
+    Error(T, Deviation<T>, Option<Deviation<T>>),
 }
 
-#[derive(Debug)]
-struct Range {
-    min: f64,
-    max: f64,
+impl<T> State<T> {
+    /// Ordinal severity used to decide when hysteresis may downgrade.
+    fn severity(&self) -> u8 {
+        match self {
+            State::Nominal(_) => 0,
+            State::Alert(..) => 1,
+            State::Error(..) => 2,
+        }
+    }
+}
+
+/// Whether a range endpoint is part of the range (`[`/`]`) or an open limit
+/// the value must stay strictly clear of (`(`/`)`).
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Bound {
+    Inclusive,
+    Exclusive,
+}
+
+impl Bound {
+    /// The stricter of two bounds — exclusive wins, used when two endpoints
+    /// coincide in an intersection.
+    fn tighter(self, other: Bound) -> Bound {
+        match (self, other) {
+            (Bound::Inclusive, Bound::Inclusive) => Bound::Inclusive,
+            _ => Bound::Exclusive,
+        }
+    }
+
+    /// The looser of two bounds — inclusive wins, used when merging endpoints
+    /// into a union.
+    fn wider(self, other: Bound) -> Bound {
+        match (self, other) {
+            (Bound::Exclusive, Bound::Exclusive) => Bound::Exclusive,
+            _ => Bound::Inclusive,
+        }
+    }
+}
+
+struct Range<T> {
+    min: T,
+    max: T,
+    min_bound: Bound,
+    max_bound: Bound,
 }
 
-impl Range {
-    fn new(min: f64, max: f64) -> Result<Range, String> {
+impl<T: std::fmt::Debug> std::fmt::Debug for Range<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let open = match self.min_bound {
+            Bound::Inclusive => '[',
+            Bound::Exclusive => '(',
+        };
+        let close = match self.max_bound {
+            Bound::Inclusive => ']',
+            Bound::Exclusive => ')',
+        };
+        write!(f, "{}{:?}, {:?}{}", open, self.min, self.max, close)
+    }
+}
+
+impl<T: Num + PartialOrd + Copy + std::fmt::Display + std::fmt::Debug> Range<T> {
+    /// Construct a range with explicit inclusivity on each endpoint.
+    fn with_bounds(
+        min: T,
+        max: T,
+        min_bound: Bound,
+        max_bound: Bound,
+    ) -> Result<Range<T>, String> {
         match min <= max {
-            true => Ok(Range { min, max }),
+            true => Ok(Range { min, max, min_bound, max_bound }),
             false => Err(format!(
                 "invalid range: min:={}, max:={}, min <= max is false!",
                 min, max
@@ -32,33 +98,112 @@ impl Range {
         }
     }
 
-    fn contains(&self, range: &Range) -> bool {
-        &self.min <= &range.min && &range.max <= &self.max
+    /// The common case: a closed `[min, max]` range.
+    fn new(min: T, max: T) -> Result<Range<T>, String> {
+        Range::with_bounds(min, max, Bound::Inclusive, Bound::Inclusive)
     }
 
-    fn deviation(&self, x: f64) -> Option<f64> {
+    fn contains(&self, range: &Range<T>) -> bool {
+        let lower_ok = self.min < range.min
+            || (self.min == range.min
+                && (self.min_bound == Bound::Inclusive || range.min_bound == Bound::Exclusive));
+        let upper_ok = range.max < self.max
+            || (range.max == self.max
+                && (self.max_bound == Bound::Inclusive || range.max_bound == Bound::Exclusive));
+        lower_ok && upper_ok
+    }
+
+    fn deviation(&self, x: T) -> Option<T> {
         if x < self.min {
-            return Some(x - self.min) 
+            return Some(x - self.min)
+        }
+        if x == self.min && self.min_bound == Bound::Exclusive {
+            return Some(T::zero())
         }
         if x > self.max {
-            return Some(x - self.max) 
+            return Some(x - self.max)
+        }
+        if x == self.max && self.max_bound == Bound::Exclusive {
+            return Some(T::zero())
         }
         None
     }
 
-    fn size(&self) -> f64 {
+    fn size(&self) -> T {
         self.max - self.min
     }
+
+    fn overlaps(&self, other: &Range<T>) -> bool {
+        self.max_of_mins(other) < self.min_of_maxs(other)
+    }
+
+    fn is_adjacent(&self, other: &Range<T>) -> bool {
+        self.min_of_maxs(other) == self.max_of_mins(other)
+    }
+
+    fn intersection(&self, other: &Range<T>) -> Option<Range<T>> {
+        // The intersection takes the greater lower and lesser upper endpoint,
+        // tightening the bound where the two meet exactly.
+        let (min, min_bound) = if self.min > other.min {
+            (self.min, self.min_bound)
+        } else if other.min > self.min {
+            (other.min, other.min_bound)
+        } else {
+            (self.min, self.min_bound.tighter(other.min_bound))
+        };
+        let (max, max_bound) = if self.max < other.max {
+            (self.max, self.max_bound)
+        } else if other.max < self.max {
+            (other.max, other.max_bound)
+        } else {
+            (self.max, self.max_bound.tighter(other.max_bound))
+        };
+        match min <= max {
+            true => Some(Range { min, max, min_bound, max_bound }),
+            false => None,
+        }
+    }
+
+    fn union(&self, other: &Range<T>) -> Option<Range<T>> {
+        if !self.overlaps(other) && !self.is_adjacent(other) {
+            return None;
+        }
+        // The union takes the lesser lower and greater upper endpoint,
+        // widening the bound where the two meet exactly.
+        let (min, min_bound) = if self.min < other.min {
+            (self.min, self.min_bound)
+        } else if other.min < self.min {
+            (other.min, other.min_bound)
+        } else {
+            (self.min, self.min_bound.wider(other.min_bound))
+        };
+        let (max, max_bound) = if self.max > other.max {
+            (self.max, self.max_bound)
+        } else if other.max > self.max {
+            (other.max, other.max_bound)
+        } else {
+            (self.max, self.max_bound.wider(other.max_bound))
+        };
+        Some(Range { min, max, min_bound, max_bound })
+    }
+
+    fn max_of_mins(&self, other: &Range<T>) -> T {
+        if self.min >= other.min { self.min } else { other.min }
+    }
+
+    fn min_of_maxs(&self, other: &Range<T>) -> T {
+        if self.max <= other.max { self.max } else { other.max }
+    }
 }
 
 #[derive(Debug)]
-struct Sample {
-    value: f64,
+struct Sample<T> {
+    value: T,
     time: time::Instant,
 }
 
-impl Sample {
-    fn new(x: usize) -> Sample {
+impl Sample<f64> {
+    fn new(x: usize) -> Sample<f64> {
         Sample {
             value: x as f64,
             time: time::Instant::now(),
@@ -67,22 +212,36 @@ impl Sample {
 }
 
 #[derive(Debug)]
-struct Monitor {
-    domain: Range,
-    destination: Range,
-    nominal: Range,
+struct Monitor<T> {
+    domain: Range<T>,
+    destination: Range<T>,
+    nominal: Range<T>,
+    alert: Range<T>,
+    margin: T,
+    rate_limit: Option<Range<T>>,
+    last: Option<State<T>>,
 }
 
-impl Monitor {
+impl<T: Num + PartialOrd + Copy + NumCast + std::fmt::Display + std::fmt::Debug> Monitor<T> {
     fn new(
-        domain: Range,
-        destination: Range,
-        nominal: Range,
-    ) -> Result<Monitor, String> {
-        if let false = &destination.contains(&nominal) {
+        domain: Range<T>,
+        destination: Range<T>,
+        nominal: Range<T>,
+        alert: Range<T>,
+        margin: T,
+        rate_limit: Option<Range<T>>,
+    ) -> Result<Monitor<T>, String> {
+        if !alert.contains(&nominal) {
             return Err(format!(
                 "values nominal:={:?} is not contained in values:={:?}",
-                &nominal, &destination
+                &nominal, &alert
+            ));
+        }
+
+        if !destination.contains(&alert) {
+            return Err(format!(
+                "values alert:={:?} is not contained in values:={:?}",
+                &alert, &destination
             ));
         }
 
@@ -90,21 +249,109 @@ impl Monitor {
             domain,
             destination,
             nominal,
+            alert,
+            margin,
+            rate_limit,
+            last: None,
         })
     }
 
-    fn f(& self, x: f64) -> f64 {
-        self.destination.min + ( x * ( self.destination.size() / self.domain.size() ) )
+    fn f(& self, x: T) -> T {
+        self.destination.min + ( x * self.destination.size() ) / self.domain.size()
     }
 
-    fn validate(&mut self, current: Sample, previous: Sample) -> State {
-        
-        let current_value = self.f(current.value);
-        let previous_value = self.f(previous.value);
+    /// Signed deviation of `mapped` from the nominal band, tagged with the
+    /// side it left on. Values inside nominal report `High(zero)` by
+    /// convention so a held state still carries a direction.
+    fn direction(&self, mapped: T) -> Deviation<T> {
+        let magnitude = self.nominal.deviation(mapped).unwrap_or_else(T::zero);
+        if mapped < self.nominal.min {
+            Deviation::Low(magnitude)
+        } else {
+            Deviation::High(magnitude)
+        }
+    }
 
-        //if current
+    /// Build the state for a given severity at `mapped`.
+    fn state_at(&self, severity: u8, mapped: T) -> State<T> {
+        match severity {
+            0 => State::Nominal(mapped),
+            1 => State::Alert(mapped, self.direction(mapped), None),
+            _ => State::Error(mapped, self.direction(mapped), None),
+        }
+    }
 
-        State::Nominal(current_value)
+    /// Classify `mapped` against the nominal and alert bands, ignoring history.
+    fn classify(&self, mapped: T) -> u8 {
+        match self.nominal.deviation(mapped) {
+            None => 0,
+            Some(_) => match self.alert.deviation(mapped) {
+                None => 1,
+                Some(_) => 2,
+            },
+        }
+    }
+
+    fn validate(&mut self, current: Sample<T>, previous: Sample<T>) -> State<T> {
+        let mapped = self.f(current.value);
+        let previous_mapped = self.f(previous.value);
+
+        let mut severity = self.classify(mapped);
+
+        // Hysteresis: severity is only downgraded one step at a time
+        // (Error->Alert->Nominal), and only once the value has re-entered the
+        // next-lower band by `margin`. The direction the previous sample was
+        // travelling picks which boundary the margin is measured from.
+        if let Some(last) = &self.last {
+            let last_severity = last.severity();
+            if severity < last_severity {
+                let step_to = last_severity - 1;
+                // The band the value must re-enter to earn this step down.
+                let band = match step_to {
+                    0 => &self.nominal,
+                    _ => &self.alert,
+                };
+                let descending = mapped < previous_mapped;
+                let reentered = if descending {
+                    mapped <= band.max - self.margin
+                } else {
+                    mapped >= band.min + self.margin
+                };
+                severity = if reentered { step_to } else { last_severity };
+            }
+        }
+
+        let mut state = self.state_at(severity, mapped);
+
+        // Rate-of-change: a value still inside the band but climbing too fast is
+        // a transient we want to flag before it breaches. The slope check is
+        // instantaneous and bypasses hysteresis so fast spikes are caught.
+        if let Some(rate_limit) = &self.rate_limit {
+            let dt = current.time.duration_since(previous.time).as_secs_f64();
+            // Divide in f64 so a sub-second dt doesn't truncate to zero (and
+            // panic) for integer domains; cast the resulting slope back to T.
+            if let (true, Some(cur), Some(prev)) =
+                (dt != 0.0, mapped.to_f64(), previous_mapped.to_f64())
+            {
+                if let Some(slope) = T::from((cur - prev) / dt) {
+                    if rate_limit.deviation(slope).is_some() {
+                        // Escalate to at least Alert. When a level violation is
+                        // already present its direction stays in the primary
+                        // slot and the rate lands in the secondary slot, so the
+                        // two violations remain distinguishable downstream.
+                        let rate = Deviation::Rate(slope);
+                        state = match state {
+                            State::Nominal(v) => State::Alert(v, rate, None),
+                            State::Alert(v, level, _) => State::Alert(v, level, Some(rate)),
+                            State::Error(v, level, _) => State::Error(v, level, Some(rate)),
+                        };
+                    }
+                }
+            }
+        }
+
+        self.last = Some(state.clone());
+        state
     }
 }
 
@@ -160,7 +407,7 @@ mod tests {
     #[test]
     fn range_deviation() {
         for off in [-1.0, -0.5, 0.0, 0.5, 1.0] {
-            let range = Range::new(off - 1.0, off + 1.0).unwrap();
+            let range = Range64::new(off - 1.0, off + 1.0).unwrap();
 
             assert_eq!(None, range.deviation(off - 1.0));
             assert_eq!(None, range.deviation(off));
@@ -170,11 +417,65 @@ mod tests {
         }
     }
     #[test]
+    fn range_set_algebra() {
+        let a = Range::new(0.0, 0.5).unwrap();
+        let b = Range::new(0.3, 0.8).unwrap();
+        let c = Range::new(0.5, 1.0).unwrap();
+        let d = Range::new(0.6, 1.0).unwrap();
+
+        // overlapping pair.
+        assert!(a.overlaps(&b));
+        assert!(!a.is_adjacent(&b));
+        let i = a.intersection(&b).unwrap();
+        assert_eq!(0.3, i.min);
+        assert_eq!(0.5, i.max);
+        let u = a.union(&b).unwrap();
+        assert_eq!(0.0, u.min);
+        assert_eq!(0.8, u.max);
+
+        // adjacent pair touching at a single point.
+        assert!(!a.overlaps(&c));
+        assert!(a.is_adjacent(&c));
+        let i = a.intersection(&c).unwrap();
+        assert_eq!(0.5, i.min);
+        assert_eq!(0.5, i.max);
+        let u = a.union(&c).unwrap();
+        assert_eq!(0.0, u.min);
+        assert_eq!(1.0, u.max);
+
+        // disjoint pair: no intersection, no union.
+        assert!(!a.overlaps(&d));
+        assert!(!a.is_adjacent(&d));
+        assert!(a.intersection(&d).is_none());
+        assert!(a.union(&d).is_none());
+    }
+    #[test]
+    fn range_bounds() {
+        let closed = Range::new(0.0, 1.0).unwrap();
+        let half_open =
+            Range::with_bounds(0.0, 1.0, Bound::Inclusive, Bound::Exclusive).unwrap();
+
+        // a closed range contains its half-open sibling, but not vice versa.
+        assert!(closed.contains(&half_open));
+        assert!(!half_open.contains(&closed));
+
+        // a value on the exclusive boundary is a zero-magnitude deviation.
+        assert_eq!(Some(0.0), half_open.deviation(1.0));
+        assert_eq!(None, closed.deviation(1.0));
+        assert_eq!(None, half_open.deviation(0.5));
+
+        // Debug makes the half-open semantics visible.
+        assert_eq!("[0.0, 1.0)", format!("{:?}", half_open));
+    }
+    #[test]
     fn new_monitor() {
         let monitor = Monitor::new(
             Range::new(0.0, 256.0).unwrap(),
             Range::new(0.0, 1.0).unwrap(),
             Range::new(0.2, 0.7).unwrap(),
+            Range::new(0.1, 0.8).unwrap(),
+            0.0,
+            None,
         )
         .unwrap();
         assert_eq!(0.0, monitor.domain.min);
@@ -183,20 +484,119 @@ mod tests {
         assert_eq!(1.0, monitor.destination.max);
         assert_eq!(0.2, monitor.nominal.min);
         assert_eq!(0.7, monitor.nominal.max);
+        assert_eq!(0.1, monitor.alert.min);
+        assert_eq!(0.8, monitor.alert.max);
     }
     #[test]
     fn new_monitor_invalid_spec() {
         let result = Monitor::new(
             Range::new(0.0, 256.0).unwrap(),
-            Range::new(0.2, 1.0).unwrap(),
+            Range::new(0.0, 1.0).unwrap(),
             Range::new(0.1, 0.7).unwrap(),
+            Range::new(0.2, 0.8).unwrap(),
+            0.0,
+            None,
         );
         match result {
             Err(str) => assert_eq!(
-                "values nominal:=Range { min: 0.1, max: 0.7 } is not contained in values:=Range { min: 0.2, max: 1.0 }",
+                "values nominal:=[0.1, 0.7] is not contained in values:=[0.2, 0.8]",
                 str
             ),
             Ok(_) => assert!(false),
         }
     }
+    #[test]
+    fn validate_classifies_bands() {
+        // domain 0..1 maps identically onto destination 0..1 via f.
+        let mut monitor = Monitor::new(
+            Range64::new(0.0, 1.0).unwrap(),
+            Range64::new(0.0, 1.0).unwrap(),
+            Range64::new(0.2, 0.7).unwrap(),
+            Range64::new(0.1, 0.8).unwrap(),
+            0.0,
+            None,
+        )
+        .unwrap();
+
+        match monitor.validate(Sample { value: 0.5, time: Instant::now() }, Sample { value: 0.5, time: Instant::now() }) {
+            State::Nominal(v) => assert!((v - 0.5).abs() < 1e-10),
+            other => panic!("expected Nominal, got {:?}", other),
+        }
+        match monitor.validate(Sample { value: 0.75, time: Instant::now() }, Sample { value: 0.75, time: Instant::now() }) {
+            State::Alert(_, Deviation::High(_), None) => {}
+            other => panic!("expected Alert(High), got {:?}", other),
+        }
+        match monitor.validate(Sample { value: 0.05, time: Instant::now() }, Sample { value: 0.75, time: Instant::now() }) {
+            State::Error(_, Deviation::Low(_), None) => {}
+            other => panic!("expected Error(Low), got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn validate_hysteresis_holds_until_margin() {
+        let mut monitor = Monitor::new(
+            Range::new(0.0, 1.0).unwrap(),
+            Range::new(0.0, 1.0).unwrap(),
+            Range::new(0.2, 0.7).unwrap(),
+            Range::new(0.1, 0.8).unwrap(),
+            0.1,
+            None,
+        )
+        .unwrap();
+
+        // Drive into Error on the high side.
+        assert_eq!(2, monitor.validate(Sample { value: 0.95, time: Instant::now() }, Sample { value: 0.5, time: Instant::now() }).severity());
+        // Descend just inside the alert band but within the margin: hold Error.
+        assert_eq!(2, monitor.validate(Sample { value: 0.79, time: Instant::now() }, Sample { value: 0.95, time: Instant::now() }).severity());
+        // Descend well inside the alert band: downgrade to Alert is allowed.
+        assert_eq!(1, monitor.validate(Sample { value: 0.65, time: Instant::now() }, Sample { value: 0.79, time: Instant::now() }).severity());
+    }
+
+    #[test]
+    fn validate_flags_rate_violation() {
+        // identity map; allow at most +/-0.1 mapped units per second.
+        let mut monitor = Monitor::new(
+            Range::new(0.0, 1.0).unwrap(),
+            Range::new(0.0, 1.0).unwrap(),
+            Range::new(0.2, 0.7).unwrap(),
+            Range::new(0.1, 0.8).unwrap(),
+            0.0,
+            Some(Range::new(-0.1, 0.1).unwrap()),
+        )
+        .unwrap();
+
+        // Both samples sit inside nominal, but the value jumped 0.4 in ~half a
+        // second: a rate violation escalates an otherwise-nominal reading.
+        let previous = Sample { value: 0.3, time: Instant::now() };
+        let current = Sample { value: 0.7, time: previous.time + std::time::Duration::from_millis(500) };
+        match monitor.validate(current, previous) {
+            State::Alert(_, Deviation::Rate(slope), None) => assert!(slope > 0.1),
+            other => panic!("expected Alert(Rate), got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn validate_keeps_level_and_rate_distinct() {
+        let mut monitor = Monitor::new(
+            Range64::new(0.0, 1.0).unwrap(),
+            Range64::new(0.0, 1.0).unwrap(),
+            Range64::new(0.2, 0.7).unwrap(),
+            Range64::new(0.1, 0.8).unwrap(),
+            0.0,
+            Some(Range64::new(-0.1, 0.1).unwrap()),
+        )
+        .unwrap();
+
+        // A leap from nominal to past the alert band: both a High level breach
+        // and a rate breach. The level direction stays primary, the slope rides
+        // along in the secondary slot.
+        let previous = Sample { value: 0.5, time: Instant::now() };
+        let current = Sample { value: 0.95, time: previous.time + std::time::Duration::from_millis(500) };
+        match monitor.validate(current, previous) {
+            State::Error(_, Deviation::High(_), Some(Deviation::Rate(slope))) => {
+                assert!(slope > 0.1)
+            }
+            other => panic!("expected Error(High, Rate), got {:?}", other),
+        }
+    }
 }